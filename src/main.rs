@@ -18,44 +18,89 @@
 #![allow(clippy::let_underscore_drop)]
 #![forbid(unsafe_code)]
 
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::Read as _;
 use std::ops::{Index, IndexMut};
 
-type Amount = u8;
+type Amount = u32;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct BoardSide {
-	bins: [Amount; 6],
+	bins: Vec<Amount>,
 	mancala: Amount,
 }
 
-impl BoardSide {
-	fn new(start_with: Amount) -> Self {
-		Self {
-			bins: [start_with; 6],
-			mancala: 0,
-		}
-	}
-}
-
 impl Index<Bin> for BoardSide {
 	type Output = Amount;
 
 	fn index(&self, bin: Bin) -> &Self::Output {
-		&self.bins[usize::from(bin as u8)]
+		&self.bins[usize::from(bin.0)]
 	}
 }
 
 impl IndexMut<Bin> for BoardSide {
 	fn index_mut(&mut self, bin: Bin) -> &mut Self::Output {
-		&mut self.bins[usize::from(bin as u8)]
+		&mut self.bins[usize::from(bin.0)]
 	}
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Board {
 	sides: [BoardSide; 2],
 }
 
+impl Board {
+	/// How many bins each side has. Both sides always agree on this.
+	fn bins_per_side(&self) -> u8 {
+		u8::try_from(self.sides[0].bins.len()).expect("bins_per_side must fit in a u8")
+	}
+
+	/// Exchanges `sides[0]` and `sides[1]`, so that whoever was "them" becomes "us".
+	///
+	/// Used to keep the solver's recursion uniform: it always reasons about `sides[0]` as the
+	/// player to move.
+	fn flip(&mut self) {
+		self.sides.swap(0, 1);
+	}
+
+	/// The game is over once one side has no seeds left to move.
+	fn is_game_over(&self) -> bool {
+		self.sides.iter().any(|side| side.bins.iter().all(|&amount| amount == 0))
+	}
+
+	/// Sweeps each side's remaining bins into that side's own mancala.
+	///
+	/// Only meaningful once [`Board::is_game_over`] holds, but it's harmless to call otherwise:
+	/// the side with no seeds left contributes nothing.
+	fn finalize(mut self) -> Self {
+		for side in &mut self.sides {
+			side.mancala += side.bins.iter().sum::<Amount>();
+			side.bins.fill(0);
+		}
+		self
+	}
+
+	/// A hash of this arrangement of seeds, for use as a transposition table key.
+	///
+	/// Hashes each side's bins followed by its mancala, in the same `sides[0]`-then-`sides[1]`
+	/// flat-index order `FlatIndex` walks the board in. Since the solver always normalizes `board`
+	/// so that `sides[0]` is the side to move (see [`Board::flip`]), that ordering already folds
+	/// the side to move into the hash.
+	///
+	/// This is only a hash, not a unique identifier: two different boards can collide on the same
+	/// `u64`. Callers that use this as a table key must still verify the cached entry's board
+	/// matches before trusting it.
+	fn transposition_key(&self) -> u64 {
+		let mut hasher = std::collections::hash_map::DefaultHasher::new();
+		for side in &self.sides {
+			side.bins.hash(&mut hasher);
+			side.mancala.hash(&mut hasher);
+		}
+		hasher.finish()
+	}
+}
+
 #[derive(Debug, Clone, Copy)]
 enum MoveResult {
 	GoAgain,
@@ -69,31 +114,42 @@ enum FlatIndexKind {
 	TheirBins(u8),
 }
 
-#[derive(Debug, Clone, Copy)]
-struct FlatIndex(u8);
+/// The largest `bins_per_side` that `FlatIndex`'s arithmetic can handle: `step`/`opposite` compute
+/// `2 * bins_per_side [+ 1]` in `u8`, which needs `bins_per_side <= (u8::MAX - 1) / 2`.
+const MAX_BINS_PER_SIDE: u8 = (u8::MAX - 1) / 2;
 
-impl From<Bin> for FlatIndex {
-	fn from(bin: Bin) -> Self {
-		Self(bin as u8)
-	}
+#[derive(Debug, Clone, Copy)]
+struct FlatIndex {
+	value: u8,
+	bins_per_side: u8,
 }
 
 impl FlatIndex {
+	fn from_bin(bin: Bin, bins_per_side: u8) -> Self {
+		Self {
+			value: bin.0,
+			bins_per_side,
+		}
+	}
+
 	fn step(&mut self) {
-		self.0 += 1;
-		self.0 %= 13;
+		self.value += 1;
+		self.value %= 2 * self.bins_per_side + 1;
 	}
 
 	fn opposite(self) -> Self {
-		Self(12 - self.0)
+		Self {
+			value: 2 * self.bins_per_side - self.value,
+			..self
+		}
 	}
 
 	fn kind(self) -> FlatIndexKind {
-		match self.0 {
-			side0 @ 0..=5 => FlatIndexKind::MyBins(side0),
-			6 => FlatIndexKind::MyMancala,
-			side1 @ 7..=12 => FlatIndexKind::TheirBins(side1 - 7),
-			_ => unreachable!(),
+		let n = self.bins_per_side;
+		match self.value {
+			side0 if side0 < n => FlatIndexKind::MyBins(side0),
+			mancala if mancala == n => FlatIndexKind::MyMancala,
+			side1 => FlatIndexKind::TheirBins(side1 - (n + 1)),
 		}
 	}
 }
@@ -123,16 +179,9 @@ impl IndexMut<FlatIndex> for Board {
 }
 
 impl Board {
-	fn new(start_with: Amount) -> Self {
-		Self {
-			sides: [BoardSide::new(start_with); 2],
-		}
-	}
-
-	fn make_move_(&mut self, index: impl Into<FlatIndex>) -> MoveResult {
+	fn make_move_(&mut self, mut index: FlatIndex) -> MoveResult {
 		// within this function `sides[0]` is the "current side"
 
-		let mut index = index.into();
 		let mut in_hand = std::mem::take(&mut self[index]);
 		while in_hand > 0 {
 			index.step();
@@ -154,68 +203,76 @@ impl Board {
 	}
 
 	fn make_move(&mut self, move_: Bin) -> Option<MoveResult> {
-		if self[move_.into()] == 0 {
+		let index = FlatIndex::from_bin(move_, self.bins_per_side());
+		if self[index] == 0 {
 			None
 		} else {
-			Some(self.make_move_(move_))
+			Some(self.make_move_(index))
 		}
 	}
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Bin {
-	A,
-	B,
-	C,
-	D,
-	E,
-	F,
-}
-
-impl TryFrom<u8> for Bin {
-	type Error = ();
+/// A bin index, validated against a particular `bins_per_side`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bin(u8);
 
-	fn try_from(v: u8) -> Result<Self, Self::Error> {
-		Bin::ALL.get(usize::from(v)).copied().ok_or(())
+impl Bin {
+	fn new(index: u8, bins_per_side: u8) -> Option<Self> {
+		(index < bins_per_side).then_some(Self(index))
 	}
 }
 
-impl Bin {
-	const ALL: [Self; 6] = [Self::A, Self::B, Self::C, Self::D, Self::E, Self::F];
+impl std::fmt::Display for Bin {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(formatter, "{}", self.0)
+	}
 }
 
 #[derive(Debug)]
 enum Tree {
 	TurnOver(Board),
-	Continue(Box<[Option<Self>; 6]>),
+	Continue(Box<[Option<Self>]>),
 }
 
 impl Tree {
 	fn build(board: Board) -> Self {
-		let results = Bin::ALL.map(|bin| {
-			let mut board = board;
-			let res = board.make_move(bin);
-			res.map(|res| match res {
-				MoveResult::GoAgain => Self::build(board),
-				MoveResult::TurnOver => Tree::TurnOver(board),
+		// A "go again" can run the current side's bins dry mid-chain, which ends the game right
+		// there rather than handing them an empty set of moves to choose from.
+		if board.is_game_over() {
+			return Tree::TurnOver(board);
+		}
+
+		let bins_per_side = board.bins_per_side();
+		let results = (0..bins_per_side)
+			.map(|index| {
+				let bin = Bin::new(index, bins_per_side).expect("index is in range by construction");
+				let mut board = board.clone();
+				let res = board.make_move(bin);
+				res.map(|res| match res {
+					MoveResult::GoAgain => Self::build(board),
+					MoveResult::TurnOver => Tree::TurnOver(board),
+				})
 			})
-		});
-		Tree::Continue(Box::new(results))
+			.collect();
+		Tree::Continue(results)
 	}
 
-	fn find_max_paths(&self) -> Vec<(Amount, Box<[Bin]>)> {
-		fn helper(tree: &Tree, path_so_far: &mut Vec<Bin>, out: &mut Vec<(Amount, Box<[Bin]>)>) {
+	/// Every `TurnOver` board reachable from `self`, paired with the sequence of bins played to
+	/// reach it (a "go again" chain can play more than one bin within a single ply).
+	fn leaves(&self) -> Vec<(Box<[Bin]>, Board)> {
+		fn helper(tree: &Tree, path_so_far: &mut Vec<Bin>, out: &mut Vec<(Box<[Bin]>, Board)>) {
 			match tree {
 				Tree::TurnOver(board) => {
-					out.push((board.sides[0].mancala, path_so_far.as_slice().into()));
+					out.push((path_so_far.as_slice().into(), board.clone()));
 				}
 				Tree::Continue(move_results) => {
+					let bins_per_side = u8::try_from(move_results.len()).expect("bins_per_side must fit in a u8");
 					for (bin, result) in move_results
 						.iter()
 						.enumerate()
 						.filter_map(|(bin, result)| Some((bin, result.as_ref()?)))
 					{
-						let bin = Bin::try_from(u8::try_from(bin).unwrap()).unwrap();
+						let bin = Bin::new(u8::try_from(bin).unwrap(), bins_per_side).unwrap();
 						path_so_far.push(bin);
 						helper(result, path_so_far, out);
 						path_so_far.pop();
@@ -227,25 +284,250 @@ impl Tree {
 		let mut path_so_far = Vec::new();
 		let mut out = Vec::new();
 		helper(self, &mut path_so_far, &mut out);
-		// order by higher amounts first, then by shorter paths first
-		out.sort_by(|(amount_a, path_a), (amount_b, path_b)| {
-			amount_a
-				.cmp(amount_b)
-				.reverse()
-				.then_with(|| path_a.len().cmp(&path_b.len()))
-		});
 		out
 	}
 }
 
+/// Bounds used to seed alpha-beta search. Kept well inside `i64` range (rather than
+/// `i64::MIN`/`MAX`) so that negating a bound can never overflow.
+const INFINITY: i64 = 1_000_000;
+
+/// Which side of `value` is trustworthy, for a value cached from an alpha-beta search that may
+/// have been cut short by pruning.
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+	/// `value` is the board's true value.
+	Exact,
+	/// The true value is at least `value` (a beta cutoff occurred).
+	Lower,
+	/// The true value is at most `value` (no move beat alpha).
+	Upper,
+}
+
+#[derive(Debug, Clone)]
+struct CachedResult {
+	/// The exact board this entry was computed for, since [`Board::transposition_key`] is only a
+	/// hash and different boards can collide on the same key.
+	board: Board,
+	/// The remaining search depth `value` was computed at; a cached value is only safe to reuse
+	/// for a search of at most this depth.
+	depth: u32,
+	value: i64,
+	bound: Bound,
+	/// The first bin of the best line found for this board, tried first the next time it's
+	/// searched (at any depth). A shallower iterative-deepening pass almost always agrees with a
+	/// deeper one about which move is best, so trying it first tends to put the strongest move
+	/// first in the loop below, which is what lets alpha-beta cut the rest of the search short.
+	best_move: Option<Bin>,
+}
+
+/// Runs negamax with alpha-beta pruning over complete games, rather than a single turn.
+///
+/// A "ply" is one full turn: the chain of moves a player makes while `MoveResult::GoAgain` keeps
+/// extending it, ending at the first `MoveResult::TurnOver`. `board` is always read as "the side
+/// to move is `sides[0]`"; [`Board::flip`] is used to hand the board to the other player between
+/// plies. `depth_limit` bounds how many plies deep the search is allowed to go, to guarantee
+/// termination; when it runs out before the game is over, the current mancala difference is used
+/// as the (inexact) value of the position. A `depth_limit` of 0 is treated as 1.
+///
+/// A transposition table collapses the many move orders (in particular, "go again" chains and
+/// symmetric openings) that reach the same board, so a position is searched again only when its
+/// cached result was computed to a shallower depth or is a bound that does not settle the current
+/// alpha-beta window. It also doubles as move ordering: each cached entry remembers the best move found
+/// for its board, which is tried first the next time that board comes up, so alpha-beta sees the
+/// strongest move first instead of in plain bin order.
+///
+/// Searches iteratively deepen from 1 ply up to `depth_limit`, reusing the same table across
+/// passes. Each pass is far cheaper than a from-scratch search to that depth, since the move
+/// ordering it seeds almost always lets the next, deeper pass cut most branches immediately; it
+/// also means a full-strength answer for every depth up to the deepest one completed is always
+/// available, rather than the search running silently for an unbounded time with nothing to show
+/// for it if it's interrupted partway through the final pass.
+///
+/// Returns the value of `board` for the side to move, and the move sequence (within the current
+/// ply) that achieves it.
+fn solve(board: &Board, depth_limit: u32) -> (i64, Vec<Bin>) {
+	fn negamax(
+		board: Board,
+		depth_limit: u32,
+		mut alpha: i64,
+		beta: i64,
+		table: &mut HashMap<u64, CachedResult>,
+	) -> (i64, Vec<Bin>) {
+		let key = board.transposition_key();
+		// `key` is only a hash, so a different board can collide on it; only trust an entry once
+		// we've confirmed it was actually computed for this board.
+		let cached = table.get(&key).filter(|cached| cached.board == board);
+		if let Some(cached) = cached {
+			let usable = cached.depth >= depth_limit
+				&& match cached.bound {
+					Bound::Exact => true,
+					Bound::Lower => cached.value >= beta,
+					Bound::Upper => cached.value <= alpha,
+				};
+			if usable {
+				return (cached.value, Vec::new());
+			}
+		}
+		let preferred_move = cached.and_then(|cached| cached.best_move);
+
+		if board.is_game_over() {
+			let finalized = board.clone().finalize();
+			let score = i64::from(finalized.sides[0].mancala) - i64::from(finalized.sides[1].mancala);
+			// The game is over, so this value holds no matter how much further we were asked to look.
+			table.insert(
+				key,
+				CachedResult {
+					board,
+					depth: u32::MAX,
+					value: score,
+					bound: Bound::Exact,
+					best_move: None,
+				},
+			);
+			return (score, Vec::new());
+		}
+		if depth_limit == 0 {
+			let score = i64::from(board.sides[0].mancala) - i64::from(board.sides[1].mancala);
+			return (score, Vec::new());
+		}
+
+		let alpha_at_entry = alpha;
+		let board_for_cache = board.clone();
+		let mut leaves = Tree::build(board).leaves();
+		if let Some(preferred_move) = preferred_move {
+			if let Some(pos) = leaves.iter().position(|(path, _)| path[0] == preferred_move) {
+				leaves.swap(0, pos);
+			}
+		}
+
+		let mut best = (-INFINITY, Vec::new());
+		for (path, mut leaf) in leaves {
+			leaf.flip();
+			let (child_score, _) = negamax(leaf, depth_limit - 1, -beta, -alpha, table);
+			let score = -child_score;
+			if score > best.0 {
+				best = (score, path.into_vec());
+			}
+			alpha = alpha.max(score);
+			if alpha >= beta {
+				break;
+			}
+		}
+
+		let bound = if best.0 <= alpha_at_entry {
+			Bound::Upper
+		} else if best.0 >= beta {
+			Bound::Lower
+		} else {
+			Bound::Exact
+		};
+		table.insert(
+			key,
+			CachedResult {
+				board: board_for_cache,
+				depth: depth_limit,
+				value: best.0,
+				bound,
+				best_move: best.1.first().copied(),
+			},
+		);
+
+		best
+	}
+
+	let mut table = HashMap::new();
+	let mut result = (0, Vec::new());
+	// Iterative deepening: search to depth 1, then 2, and so on up to `depth_limit`, reusing
+	// `table` between passes. Each pass's result is printed as it completes, so a full position
+	// search that's still running (the full opening of a standard-sized board is a lot of
+	// positions) always has its best answer so far available rather than nothing at all.
+	for depth in 1..=depth_limit.max(1) {
+		result = negamax(board.clone(), depth, -INFINITY, INFINITY, &mut table);
+		eprint!("depth {depth}: {} via ", result.0);
+		for bin in &result.1 {
+			eprint!("{bin} ");
+		}
+		eprintln!();
+	}
+	result
+}
+
+/// Parses a board position from the whitespace-delimited format:
+/// `bins_per_side side0_bins... side0_mancala side1_bins... side1_mancala`.
+fn parse_board(input: &str) -> Board {
+	let mut tokens = input.split_ascii_whitespace();
+
+	// Parsed separately from the seed counts (and as a `u8`, not `Amount`): `bins_per_side` becomes
+	// the length of every `bins` vec, so it must fit the `u8` that `Board::bins_per_side` and
+	// `FlatIndex` assume, independent of how wide `Amount` is.
+	let bins_per_side: u8 = tokens
+		.next()
+		.expect("missing bins_per_side")
+		.parse()
+		.expect("bins_per_side must fit in a u8");
+	assert!(
+		bins_per_side <= MAX_BINS_PER_SIDE,
+		"bins_per_side must be at most {MAX_BINS_PER_SIDE} for FlatIndex's arithmetic to not overflow"
+	);
+
+	let mut numbers = tokens.map(|token| token.parse::<Amount>().expect("expected a number"));
+	let mut read_side = || BoardSide {
+		bins: (0..bins_per_side)
+			.map(|_| numbers.next().expect("missing bin seed count"))
+			.collect(),
+		mancala: numbers.next().expect("missing mancala seed count"),
+	};
+	let side0 = read_side();
+	let side1 = read_side();
+	Board {
+		sides: [side0, side1],
+	}
+}
+
 fn main() {
-	let tree = Tree::build(Board::new(4));
-	let paths = tree.find_max_paths();
-	for (amount, path) in &paths[..std::cmp::min(10, paths.len())] {
-		print!("{amount} via ");
-		for bin in path.iter() {
-			print!("{bin:?}");
-		}
-		println!();
+	let mut input = String::new();
+	std::io::stdin().read_to_string(&mut input).expect("failed to read stdin");
+	let board = parse_board(&input);
+
+	// Twice the total seed count is a generous bound on how many plies a game can take. Summed as
+	// `u64` since `Amount` alone has no headroom left to add into.
+	let total_seeds: u64 = board
+		.sides
+		.iter()
+		.flat_map(|side| side.bins.iter().copied().chain(std::iter::once(side.mancala)))
+		.map(u64::from)
+		.sum();
+	let depth_limit = u32::try_from(total_seeds.saturating_mul(2)).unwrap_or(u32::MAX);
+
+	let (margin, path) = solve(&board, depth_limit);
+	print!("{margin} via ");
+	for bin in &path {
+		print!("{bin} ");
+	}
+	println!();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Margins and paths below are hand-traced through `Board::make_move_`'s sowing/capture rules,
+	// not just pinned to whatever the solver happens to output.
+
+	#[test]
+	fn solves_one_bin_board() {
+		let board = parse_board("1 3 0 3 0");
+		let (margin, path) = solve(&board, 10);
+		assert_eq!(margin, 6);
+		assert_eq!(path, vec![Bin(0)]);
+	}
+
+	#[test]
+	fn solves_two_bin_board() {
+		let board = parse_board("2 1 1 0 1 1 0");
+		let (margin, path) = solve(&board, 10);
+		assert_eq!(margin, 4);
+		assert_eq!(path, vec![Bin(0)]);
 	}
 }